@@ -1,62 +1,80 @@
 use core::fmt;
-use std::{collections::VecDeque, sync::Arc, time::Duration};
+use std::{collections::VecDeque, sync::{Arc, Mutex}, time::Duration};
 
 use scylla::{prepared_statement::PreparedStatement, routing::Shard, Session};
 use tokio::{sync::oneshot::{self, error::TryRecvError}, time::Instant};
 use tracing::{debug, info};
 
+use crate::scylladb::dlq::{DlqRecord, DlqSink, NoopDlqSink};
+use crate::scylladb::metrics::ShardMetrics;
 use crate::scylladb::types::{BlockchainEvent, BlockchainEventType, ProducerId, ShardId, ShardOffset, ShardPeriod, SHARD_OFFSET_MODULO};
 
 const MICRO_BATCH_SIZE: usize = 40;
 
-pub const GET_NEW_TRANSACTION_EVENT: &str = r###"
-    SELECT
-        shard_id,
-        period,
-        producer_id,
-        offset,
-        slot,
-        event_type,
+/// Once a [`ShardIteratorState::Streaming`] buffer drops to this many rows, the next
+/// micro-batch is prefetched in the background so it's ready by the time the buffer drains.
+const PREFETCH_LOW_WATER_MARK: usize = 8;
 
-        pubkey,
-        lamports,
-        owner,
-        executable,
-        rent_epoch,
-        write_version,
-        data,
-        txn_signature,
-
-        signature,
-        signatures,
-        num_required_signatures,
-        num_readonly_signed_accounts,
-        num_readonly_unsigned_accounts,
-        account_keys,
-        recent_blockhash,
-        instructions,
-        versioned,
-        address_table_lookups,
-        meta,
-        is_vote,
-        tx_index
-    FROM log
-    WHERE producer_id = ? and shard_id = ? and offset > ? and period = ?
-    and event_type = 1
-    ORDER BY offset ASC
-    ALLOW FILTERING
-"###;
+/// Bounded retry policy applied to ScyllaDB reads performed by [`ShardIterator`] before a
+/// failure is routed to the dead-letter sink.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RetryConfig {
+    pub(crate) max_attempts: usize,
+    pub(crate) base_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Exponential backoff delay before retry attempt number `attempt` (1-indexed).
+    fn backoff(&self, attempt: usize) -> Duration {
+        // `Duration * u32` panics on overflow, and with a large `max_attempts` the factor itself
+        // overflows `u32`, so both steps are checked and fall back to the max delay instead.
+        2u32.checked_pow((attempt - 1) as u32)
+            .and_then(|factor| self.base_delay.checked_mul(factor))
+            .unwrap_or(Duration::MAX)
+    }
+}
 
 const PRODUCER_SHARD_PERIOD_COMMIT_EXISTS: &str = r###"
     SELECT
         producer_id
     FROM producer_period_commit_log
-    WHERE 
+    WHERE
         producer_id = ?
         AND shard_id = ?
         AND period = ?
 "###;
 
+const PRODUCER_SHARD_LATEST_COMMITTED_PERIOD: &str = r###"
+    SELECT period
+    FROM producer_period_commit_log
+    WHERE
+        producer_id = ?
+        AND shard_id = ?
+    ORDER BY period DESC
+    LIMIT 1
+"###;
+
+/// Where a newly constructed [`ShardIterator`] should attach.
+pub(crate) enum ShardBootstrap {
+    /// Walk every period from the very beginning, confirming each via
+    /// `producer_period_commit_log` as today.
+    FromEarliest,
+    /// Resume from a previously recorded offset, same as the original behavior.
+    FromOffset(ShardOffset),
+    /// Skip straight to the newest fully-committed period boundary, bypassing the
+    /// `Empty -> Loading -> ConfirmingPeriod` churn for history the caller doesn't want replayed.
+    FromLatestCommitted,
+}
+
 
 /// Empty : the shard iterator is either brand new or no more row are available in its inner row stream.
 /// Loading : We asked for a row iterator that may take some time to resolve but we don't want to block a consumer.
@@ -64,10 +82,13 @@ const PRODUCER_SHARD_PERIOD_COMMIT_EXISTS: &str = r###"
 /// EndOfPeriod : No more data for the current "period", we need to go back to the end Empty tate.
 enum ShardIteratorState {
     Empty(ShardOffset),
-    Loading(ShardOffset, oneshot::Receiver<VecDeque<BlockchainEvent>>),
+    Loading(ShardOffset, oneshot::Receiver<VecDeque<BlockchainEvent>>, Instant),
     Loaded(ShardOffset, VecDeque<BlockchainEvent>),
     ConfirmingPeriod(ShardOffset, oneshot::Receiver<bool>),
-    Streaming(ShardOffset, VecDeque<BlockchainEvent>),
+    /// The optional third field is a prefetch for the micro-batch following this one, kicked
+    /// off once the buffer drops to [`PREFETCH_LOW_WATER_MARK`] rows or fewer so the next read overlaps
+    /// with draining the current buffer instead of stalling on it.
+    Streaming(ShardOffset, VecDeque<BlockchainEvent>, Option<(oneshot::Receiver<VecDeque<BlockchainEvent>>, Instant)>),
     WaitingEndOfPeriod(ShardOffset, oneshot::Receiver<bool>),
 }
 
@@ -75,23 +96,35 @@ impl fmt::Debug for ShardIteratorState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Empty(arg0) => f.debug_tuple("Empty").field(arg0).finish(),
-            Self::Loading(arg0, _) => f.debug_tuple("Loading").field(arg0).finish(),
+            Self::Loading(arg0, _, _) => f.debug_tuple("Loading").field(arg0).finish(),
             Self::Loaded(arg0, _) => f.debug_tuple("Loading").field(arg0).finish(),
             Self::ConfirmingPeriod(arg0, _) => f.debug_tuple("Loading").field(arg0).finish(),
-            Self::Streaming(arg0, _) => f.debug_tuple("Available").field(arg0).finish(),
+            Self::Streaming(arg0, _, _) => f.debug_tuple("Available").field(arg0).finish(),
             Self::WaitingEndOfPeriod(arg0, _) => f.debug_tuple("EndOfPeriod").field(arg0).finish(),
         }
     }
 }
 
 impl ShardIteratorState {
+    /// Metric-friendly name, used as the statsd tag for state-transition counters.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Empty(_) => "empty",
+            Self::Loading(_, _, _) => "loading",
+            Self::Loaded(_, _) => "loaded",
+            Self::ConfirmingPeriod(_, _) => "confirming_period",
+            Self::Streaming(_, _, _) => "streaming",
+            Self::WaitingEndOfPeriod(_, _) => "waiting_end_of_period",
+        }
+    }
+
     fn last_offset(&self) -> ShardOffset {
         match self {
             Self::Empty(offset) => *offset,
-            Self::Loading(offset, _) => *offset,
+            Self::Loading(offset, _, _) => *offset,
             Self::Loaded(offset, _) => *offset,
             Self::ConfirmingPeriod(offset, _) => *offset,
-            Self::Streaming(offset, _) => *offset,
+            Self::Streaming(offset, _, _) => *offset,
             Self::WaitingEndOfPeriod(offset, _) => *offset,
         }
     }
@@ -111,6 +144,17 @@ pub(crate) struct ShardFilter {
     pub(crate) tx_account_keys: Vec<Vec<u8>>,
     pub(crate) account_owners: Vec<Vec<u8>>,
     pub(crate) account_pubkyes: Vec<Vec<u8>>,
+    /// Only keep `AccountUpdate` rows whose `data` column is exactly this many bytes.
+    pub(crate) data_size: Option<u64>,
+    /// Only keep `AccountUpdate` rows whose `data` matches `bytes` starting at `offset`, for every entry.
+    pub(crate) memcmp: Vec<(usize, Vec<u8>)>,
+    /// Drop `NewTransaction` rows where `is_vote` is true.
+    pub(crate) exclude_votes: bool,
+    /// Only keep `NewTransaction` rows whose `signature` is in this set.
+    pub(crate) tx_signatures: Vec<Vec<u8>>,
+    /// Only keep `NewTransaction` rows whose `account_keys` or instruction program ids contain at
+    /// least one of these program ids.
+    pub(crate) tx_program_ids: Vec<Vec<u8>>,
 }
 
 
@@ -124,28 +168,60 @@ pub(crate) struct ShardIterator {
     period_commit_exists_prepared_stmt: PreparedStatement,
     last_period_confirmed: ShardPeriod,
     filter: ShardFilter,
+    retry_config: RetryConfig,
+    dlq: Arc<dyn DlqSink>,
+    metrics: Option<Arc<ShardMetrics>>,
+    /// Set by a background read that gave up and dead-lettered its period, so `try_next` can
+    /// force that period past confirmation instead of re-issuing the same failing read forever.
+    dlq_skip_period: Arc<Mutex<Option<ShardPeriod>>>,
 }
 
 
 
 impl ShardIterator {
+    /// `bootstrap` replaced the old bare `offset: ShardOffset` parameter (see [`ShardBootstrap`]),
+    /// and `retry_config`/`dlq`/`metrics` are new trailing `Option`s after the existing `filter`,
+    /// all defaulted when `None`. This source tree carries no caller of `ShardIterator::new` to migrate; any
+    /// downstream consumer-setup code constructing one with the old positional `offset` argument
+    /// needs to pass `ShardBootstrap::FromOffset(offset)` instead.
     pub(crate) async fn new(
         session: Arc<Session>,
         producer_id: ProducerId,
         shard_id: ShardId,
-        offset: ShardOffset,
+        bootstrap: ShardBootstrap,
         event_type: BlockchainEventType,
         filter: Option<ShardFilter>,
+        retry_config: Option<RetryConfig>,
+        dlq: Option<Arc<dyn DlqSink>>,
+        metrics: Option<Arc<ShardMetrics>>,
     ) -> anyhow::Result<Self> {
         let mut get_events_ps = if event_type == BlockchainEventType::AccountUpdate {
             let query_str = forge_account_upadate_event_query(filter.clone().unwrap_or_default());
             session.prepare(query_str).await?
         } else {
-            session.prepare(GET_NEW_TRANSACTION_EVENT).await?
+            let query_str = forge_new_transaction_event_query(filter.clone().unwrap_or_default());
+            session.prepare(query_str).await?
         };
 
         let period_commit_exists_ps = session.prepare(PRODUCER_SHARD_PERIOD_COMMIT_EXISTS).await?;
 
+        let (offset, last_period_confirmed) = match bootstrap {
+            ShardBootstrap::FromEarliest => (0, -1),
+            ShardBootstrap::FromOffset(offset) => (offset, period_before_offset(offset)),
+            ShardBootstrap::FromLatestCommitted => {
+                let latest_committed_period_ps = session.prepare(PRODUCER_SHARD_LATEST_COMMITTED_PERIOD).await?;
+                let latest_period = session
+                    .execute(&latest_committed_period_ps, (producer_id, shard_id))
+                    .await?
+                    .maybe_first_row_typed::<(ShardPeriod,)>()?
+                    .map(|(period,)| period);
+                match latest_period {
+                    Some(period) => (period_end_offset(period), period),
+                    None => (0, -1),
+                }
+            }
+        };
+
         Ok(ShardIterator {
             session,
             producer_id,
@@ -154,8 +230,12 @@ impl ShardIterator {
             event_type,
             get_events_prepared_stmt: get_events_ps,
             period_commit_exists_prepared_stmt: period_commit_exists_ps,
-            last_period_confirmed: (offset / SHARD_OFFSET_MODULO) - 1,
+            last_period_confirmed,
             filter: filter.unwrap_or_default(),
+            retry_config: retry_config.unwrap_or_default(),
+            dlq: dlq.unwrap_or_else(|| Arc::new(NoopDlqSink)),
+            metrics,
+            dlq_skip_period: Arc::new(Mutex::new(None)),
         })
     }
 
@@ -163,6 +243,20 @@ impl ShardIterator {
         self.inner.last_offset()
     }
 
+    /// Computes the ScyllaDB shard that owns `(producer_id, shard_id, period)` via token-aware
+    /// routing. The driver itself already picks the connection for `session.execute` from the
+    /// prepared statement's token, so this doesn't feed back into routing — it only exists to
+    /// let the metrics layer tag reads by the shard they landed on.
+    fn shard_for(&self, producer_id: ProducerId, shard_id: ShardId, offset: ShardOffset, period: ShardPeriod) -> Option<Shard> {
+        let token = self
+            .get_events_prepared_stmt
+            .calculate_token(&(producer_id, shard_id, offset, period))
+            .ok()
+            .flatten()?;
+        let node = self.session.get_cluster_data().get_token_endpoints(token).into_iter().next()?;
+        node.sharder()?.shard_of(token)
+    }
+
     ///
     /// If the state of the shard iterator is [[`ShardIteratorState::Empty`]] it loads the scylladb row iterator, otherwise nothing.
     pub(crate) async fn warm(&mut self) -> anyhow::Result<()> {
@@ -172,7 +266,7 @@ impl ShardIterator {
         let last_offset = self.inner.last_offset();
 
         let micro_batch = self.fetch_micro_batch(last_offset).await?;
-        let new_state = ShardIteratorState::Streaming(last_offset, micro_batch);
+        let new_state = ShardIteratorState::Streaming(last_offset, micro_batch, None);
         self.inner = new_state;
         Ok(())
     }
@@ -183,16 +277,41 @@ impl ShardIterator {
         let ps = self.period_commit_exists_prepared_stmt.clone();
         let shard_id = self.shard_id;
         let period = last_offset / SHARD_OFFSET_MODULO;
+        let event_type = self.event_type;
+        let retry_config = self.retry_config;
+        let dlq = Arc::clone(&self.dlq);
+        let dlq_skip_period = Arc::clone(&self.dlq_skip_period);
         let (sender, receiver) = oneshot::channel();
-        let _handle: tokio::task::JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
-            let result = session
-                .execute(&ps, (producer_id, shard_id, period))
-                .await?
-                .maybe_first_row()?
-                .map(|_row| true)
-                .unwrap_or(false);
-            sender.send(result).map_err(|_| anyhow::anyhow!("failed to send back period commit status to shard iterator {}", shard_id))?;
-            Ok(())
+        let _handle: tokio::task::JoinHandle<()> = tokio::spawn(async move {
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                match session.execute(&ps, (producer_id, shard_id, period)).await {
+                    Ok(qr) => match qr.maybe_first_row() {
+                        Ok(maybe_row) => {
+                            let _ = sender.send(maybe_row.is_some());
+                            return;
+                        }
+                        Err(e) => {
+                            if attempts >= retry_config.max_attempts {
+                                dead_letter(dlq.as_ref(), producer_id, shard_id, last_offset, period, event_type, e.into(), attempts);
+                                mark_period_skipped(&dlq_skip_period, period);
+                                let _ = sender.send(false);
+                                return;
+                            }
+                        }
+                    },
+                    Err(e) => {
+                        if attempts >= retry_config.max_attempts {
+                            dead_letter(dlq.as_ref(), producer_id, shard_id, last_offset, period, event_type, e.into(), attempts);
+                            mark_period_skipped(&dlq_skip_period, period);
+                            let _ = sender.send(false);
+                            return;
+                        }
+                    }
+                }
+                tokio::time::sleep(retry_config.backoff(attempts)).await;
+            }
         });
         receiver
     }
@@ -203,21 +322,56 @@ impl ShardIterator {
         let ps = self.get_events_prepared_stmt.clone();
         let shard_id = self.shard_id;
         let session = Arc::clone(&self.session);
+        let event_type = self.event_type;
+        let retry_config = self.retry_config;
+        let dlq = Arc::clone(&self.dlq);
+        let dlq_skip_period = Arc::clone(&self.dlq_skip_period);
+
+        if let Some(shard) = self.shard_for(producer_id, shard_id, last_offset, period) {
+            if let Some(metrics) = &self.metrics {
+                metrics.record_routing_shard(producer_id, shard_id, shard);
+            }
+        }
+
         let (sender, receiver) = oneshot::channel();
-        let _: tokio::task::JoinHandle<anyhow::Result<()>> = tokio::spawn(async move {
-            let micro_batch = session
-                .execute(&ps, (producer_id, shard_id, last_offset, period))
-                .await?
-                .rows_typed_or_empty::<BlockchainEvent>().collect::<Result<VecDeque<_>, _>>()?;
-            sender.send(micro_batch).map_err(|_| anyhow::anyhow!("Failed to send micro batch to shard iterator {}", shard_id))?;
-            Ok(())
+        let _: tokio::task::JoinHandle<()> = tokio::spawn(async move {
+            let mut attempts = 0;
+            loop {
+                attempts += 1;
+                let attempt_result: anyhow::Result<VecDeque<BlockchainEvent>> = async {
+                    let micro_batch = session
+                        .execute(&ps, (producer_id, shard_id, last_offset, period))
+                        .await?
+                        .rows_typed_or_empty::<BlockchainEvent>().collect::<Result<VecDeque<_>, _>>()?;
+                    Ok(micro_batch)
+                }.await;
+
+                match attempt_result {
+                    Ok(micro_batch) => {
+                        let _ = sender.send(micro_batch);
+                        return;
+                    }
+                    Err(e) => {
+                        if attempts >= retry_config.max_attempts {
+                            dead_letter(dlq.as_ref(), producer_id, shard_id, last_offset, period, event_type, e, attempts);
+                            // An empty batch alone doesn't advance anything: `period` is marked
+                            // skipped so `try_next` force-confirms it instead of re-issuing this
+                            // same failing read at the same offset forever.
+                            mark_period_skipped(&dlq_skip_period, period);
+                            let _ = sender.send(VecDeque::new());
+                            return;
+                        }
+                    }
+                }
+                tokio::time::sleep(retry_config.backoff(attempts)).await;
+            }
         });
         receiver
     }
 
     ///
     /// Apply any filter that can not be pushdown to scylladb
-    /// 
+    ///
     fn filter_row(&self, row: BlockchainEvent) -> Option<BlockchainEvent> {
         if row.event_type == BlockchainEventType::NewTransaction {
             // Apply transaction filter here
@@ -225,7 +379,7 @@ impl ShardIterator {
             if !elligible_acc_keys.is_empty() {
                 let is_row_elligible = row.account_keys
                     .as_ref()
-                    .filter(|actual_keys| 
+                    .filter(|actual_keys|
                         actual_keys
                             .iter()
                             .find(|account_key| elligible_acc_keys.contains(account_key))
@@ -237,12 +391,86 @@ impl ShardIterator {
                     return None;
                 }
             }
+
+            // Vote exclusion and the signature IN-list are also pushed down into the query
+            // built by `forge_new_transaction_event_query`; these checks are a fallback for
+            // whatever the query couldn't already enforce.
+            if self.filter.exclude_votes && row.is_vote.unwrap_or(false) {
+                return None;
+            }
+
+            if !self.filter.tx_signatures.is_empty() {
+                let is_row_elligible = row.signature
+                    .as_ref()
+                    .map(|signature| self.filter.tx_signatures.contains(signature))
+                    .unwrap_or(false);
+                if !is_row_elligible {
+                    return None;
+                }
+            }
+
+            // Program-id membership can't be pushed down: it requires scanning the row rather
+            // than an equality/IN match. A program invoked by an instruction isn't guaranteed to
+            // show up in `account_keys` alone (e.g. it can be loaded only through
+            // `address_table_lookups`), so `instructions` has to be scanned too or such a
+            // subscriber would silently see nothing.
+            if !self.filter.tx_program_ids.is_empty() {
+                let matches_account_keys = row.account_keys
+                    .as_ref()
+                    .map(|account_keys| account_keys.iter().any(|key| self.filter.tx_program_ids.contains(key)))
+                    .unwrap_or(false);
+                let matches_instructions = row.instructions
+                    .as_ref()
+                    .map(|instructions| instructions.iter().any(|ix| self.filter.tx_program_ids.contains(&ix.program_id)))
+                    .unwrap_or(false);
+                if !matches_account_keys && !matches_instructions {
+                    return None;
+                }
+            }
+        }
+
+        if row.event_type == BlockchainEventType::AccountUpdate {
+            // memcmp/dataSize can't be expressed as a ScyllaDB WHERE clause, so evaluate them here.
+            let data = row.data.as_deref().unwrap_or(&[]);
+            if let Some(data_size) = self.filter.data_size {
+                if data.len() as u64 != data_size {
+                    return None;
+                }
+            }
+            for (offset, bytes) in &self.filter.memcmp {
+                let matches = offset
+                    .checked_add(bytes.len())
+                    .filter(|end| *end <= data.len())
+                    .map(|end| &data[*offset..end] == bytes.as_slice())
+                    .unwrap_or(false);
+                if !matches {
+                    return None;
+                }
+            }
         }
 
         Some(row)
     }
 
     pub(crate) async fn try_next(&mut self) -> anyhow::Result<Option<BlockchainEvent>> {
+        if let Some(skipped_period) = self.dlq_skip_period.lock().unwrap().take() {
+            if skipped_period > self.last_period_confirmed {
+                self.last_period_confirmed = skipped_period;
+            }
+            // Force straight past the skipped period instead of going through the normal
+            // `Loaded`/`ConfirmingPeriod` advance: that logic derives the next period from
+            // wherever `last_offset` already sits, which is one period *behind* `skipped_period`
+            // when the dead-lettered read was a period's very first (issued from
+            // `Empty(previous_period_end)`) — it would otherwise re-derive the same offset and
+            // re-issue the identical failing read forever.
+            let skip_to_offset = period_end_offset(skipped_period);
+            // `>=`: an iterator already sitting on the period end (e.g. `WaitingEndOfPeriod` whose
+            // commit check was dead-lettered) must also be reset, or it re-issues that check.
+            if skip_to_offset >= self.inner.last_offset() {
+                self.inner = ShardIteratorState::Empty(skip_to_offset);
+            }
+        }
+
         let last_offset = self.inner.last_offset();
         let current_state =
             std::mem::replace(&mut self.inner, ShardIteratorState::Empty(last_offset));
@@ -250,27 +478,29 @@ impl ShardIterator {
         let (next_state, maybe_to_return) = match current_state {
             ShardIteratorState::Empty(last_offset) => {
                 let receiver = self.fetch_micro_batch(last_offset);
-                (ShardIteratorState::Loading(last_offset, receiver), None)
+                (ShardIteratorState::Loading(last_offset, receiver, Instant::now()), None)
             },
-            ShardIteratorState::Loading(last_offset, mut receiver) => {
+            ShardIteratorState::Loading(last_offset, mut receiver, started_at) => {
                 let result = receiver.try_recv();
                 match result {
-                    Err(TryRecvError::Empty) => (ShardIteratorState::Loading(last_offset, receiver), None),
+                    Err(TryRecvError::Empty) => (ShardIteratorState::Loading(last_offset, receiver, started_at), None),
                     Err(TryRecvError::Closed) => anyhow::bail!("failed to receive micro batch"),
                     Ok(micro_batch) => {
+                        if let Some(metrics) = &self.metrics {
+                            metrics.record_fetch_latency(started_at.elapsed());
+                        }
                         (ShardIteratorState::Loaded(last_offset, micro_batch), None)
-                    } 
+                    }
                 }
             },
             ShardIteratorState::Loaded(last_offset, mut micro_batch) => {
                 let maybe_row = micro_batch.pop_front();
                 if let Some(row) = maybe_row  {
-                    (ShardIteratorState::Streaming(row.offset, micro_batch), Some(row))
+                    (ShardIteratorState::Streaming(row.offset, micro_batch, None), Some(row))
                 } else {
                     let curr_period = last_offset / SHARD_OFFSET_MODULO;
                     if curr_period <= self.last_period_confirmed {
-                        let last_period_offset = ((curr_period + 1) * SHARD_OFFSET_MODULO) - 1;
-                        (ShardIteratorState::Empty(last_period_offset), None)
+                        (ShardIteratorState::Empty(period_end_offset(curr_period)), None)
                     } else {
                         // If a newly loaded row stream is already empty, we must figure out if
                         // its because there no more data in the period or is it because we consume too fast and we should try again later.
@@ -291,17 +521,45 @@ impl ShardIterator {
                     } 
                 }
             }
-            ShardIteratorState::Streaming(last_offset, mut micro_batch) => {
+            ShardIteratorState::Streaming(last_offset, mut micro_batch, mut prefetch) => {
+                // Kick off the next micro-batch before the buffer fully drains so the read
+                // overlaps with consuming what's already here. A period boundary still has to
+                // go through the commit-confirmation path below, so don't bother prefetching it.
+                // The boundary check must be against the *buffered* back offset, not `last_offset`
+                // (the offset already consumed): the prefetch is issued from the back offset, so
+                // gating on `last_offset` alone can launch a next-period prefetch whose result then
+                // gets silently dropped once consumption reaches the period end and takes the
+                // `WaitingEndOfPeriod` branch below instead.
+                if prefetch.is_none()
+                    && !micro_batch.is_empty()
+                    && micro_batch.len() <= PREFETCH_LOW_WATER_MARK
+                {
+                    let last_buffered_offset = micro_batch.back().map(|row| row.offset).unwrap_or(last_offset);
+                    if (last_buffered_offset + 1) % SHARD_OFFSET_MODULO != 0 {
+                        let receiver = self.fetch_micro_batch(last_buffered_offset);
+                        prefetch = Some((receiver, Instant::now()));
+                    }
+                }
+
                 let maybe_row = micro_batch.pop_front();
                 if let Some(row) = maybe_row {
-                    (ShardIteratorState::Streaming(row.offset, micro_batch), Some(row))
-                } else {
-                    if (last_offset + 1) % SHARD_OFFSET_MODULO == 0 {
-                        let receiver = self.is_period_committed(last_offset);
-                        (ShardIteratorState::WaitingEndOfPeriod(last_offset, receiver), None)
-                    } else {
-                        (ShardIteratorState::Empty(last_offset), None)
+                    (ShardIteratorState::Streaming(row.offset, micro_batch, prefetch), Some(row))
+                } else if (last_offset + 1) % SHARD_OFFSET_MODULO == 0 {
+                    let receiver = self.is_period_committed(last_offset);
+                    (ShardIteratorState::WaitingEndOfPeriod(last_offset, receiver), None)
+                } else if let Some((mut receiver, started_at)) = prefetch {
+                    match receiver.try_recv() {
+                        Err(TryRecvError::Empty) => (ShardIteratorState::Streaming(last_offset, micro_batch, Some((receiver, started_at))), None),
+                        Err(TryRecvError::Closed) => anyhow::bail!("failed to receive prefetched micro batch"),
+                        Ok(next_micro_batch) => {
+                            if let Some(metrics) = &self.metrics {
+                                metrics.record_fetch_latency(started_at.elapsed());
+                            }
+                            (ShardIteratorState::Streaming(last_offset, next_micro_batch, None), None)
+                        }
                     }
+                } else {
+                    (ShardIteratorState::Empty(last_offset), None)
                 }
             },
             ShardIteratorState::WaitingEndOfPeriod(last_offset, mut rx) => {
@@ -321,12 +579,69 @@ impl ShardIterator {
                 }
             }
         };
+
+        if let Some(metrics) = &self.metrics {
+            metrics.record_state_transition(next_state.name());
+            let last_committed_offset = ((self.last_period_confirmed + 1) * SHARD_OFFSET_MODULO) - 1;
+            metrics.set_lag(self.producer_id, self.shard_id, next_state.last_offset() - last_committed_offset);
+        }
+
         let _ = std::mem::replace(&mut self.inner, next_state);
-        Ok(maybe_to_return.and_then(|row| self.filter_row(row)))
+        let row = maybe_to_return.and_then(|row| self.filter_row(row));
+        if row.is_some() {
+            if let Some(metrics) = &self.metrics {
+                metrics.record_rows_emitted(self.producer_id, self.shard_id, 1);
+            }
+        }
+        Ok(row)
     }
 }
 
 
+/// Routes a permanently-failed read to the dead-letter sink instead of tearing down the stream.
+fn dead_letter(
+    dlq: &dyn DlqSink,
+    producer_id: ProducerId,
+    shard_id: ShardId,
+    offset: ShardOffset,
+    period: ShardPeriod,
+    event_type: BlockchainEventType,
+    error: anyhow::Error,
+    attempts: usize,
+) {
+    dlq.send(DlqRecord {
+        producer_id,
+        shard_id,
+        offset,
+        period,
+        event_type,
+        error: error.to_string(),
+        attempts,
+    });
+}
+
+/// Records that `period` should be treated as skipped next time `try_next` runs, so a
+/// permanently-failed read doesn't get re-issued against the same offset forever.
+fn mark_period_skipped(dlq_skip_period: &Mutex<Option<ShardPeriod>>, period: ShardPeriod) {
+    let mut guard = dlq_skip_period.lock().unwrap();
+    if guard.map_or(true, |skipped| period > skipped) {
+        *guard = Some(period);
+    }
+}
+
+/// The last offset belonging to `period`. Used both to derive a bootstrap offset from
+/// `producer_period_commit_log` and to jump straight past a confirmed or skipped period,
+/// regardless of where within it (mid-period or the very first offset) the caller currently sits.
+fn period_end_offset(period: ShardPeriod) -> ShardOffset {
+    ((period + 1) * SHARD_OFFSET_MODULO) - 1
+}
+
+/// The last confirmed period for a bootstrap starting mid-stream at `offset`: the period strictly
+/// before the one `offset` falls in, since that's the newest period we can be sure is complete.
+fn period_before_offset(offset: ShardOffset) -> ShardPeriod {
+    (offset / SHARD_OFFSET_MODULO) - 1
+}
+
 const LOG_PRIMARY_KEY_CONDITION: &str = r###"
     producer_id = ? and shard_id = ? and offset > ? and period = ?
 "###;
@@ -416,3 +731,117 @@ fn forge_account_upadate_event_query(filter: ShardFilter) -> String {
     )
 }
 
+fn forge_new_transaction_event_query(filter: ShardFilter) -> String {
+    let mut conds = vec![];
+
+    if filter.exclude_votes {
+        conds.push("AND is_vote = false".to_owned());
+    }
+
+    let signatures = filter.tx_signatures
+        .iter()
+        .map(|signature| format_as_scylla_hexstring(signature.as_slice()))
+        .collect::<Vec<_>>();
+
+    if !signatures.is_empty() {
+        let cond = format!("AND signature IN ({})", signatures.join(", "));
+        conds.push(cond);
+    }
+    let conds_string = conds.join(" ");
+
+    format!(
+        r###"
+        SELECT
+        {projection}
+        FROM log
+        WHERE {primary_key_cond}
+        AND event_type = 1
+        {other_conds}
+        ORDER BY offset ASC
+        ALLOW FILTERING
+        "###,
+        projection = LOG_PROJECTION,
+        primary_key_cond = LOG_PRIMARY_KEY_CONDITION,
+        other_conds = conds_string,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_exponentially_from_base_delay() {
+        let retry_config = RetryConfig {
+            max_attempts: 10,
+            base_delay: Duration::from_millis(100),
+        };
+        assert_eq!(retry_config.backoff(1), Duration::from_millis(100));
+        assert_eq!(retry_config.backoff(2), Duration::from_millis(200));
+        assert_eq!(retry_config.backoff(3), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn backoff_saturates_instead_of_panicking_on_overflow() {
+        // Regression test: `Duration * u32` panics on overflow, and a large enough `attempt`
+        // (a caller-configurable `max_attempts`) used to hit that even after the multiplier
+        // itself was saturated.
+        let retry_config = RetryConfig {
+            max_attempts: usize::MAX,
+            base_delay: Duration::from_millis(100),
+        };
+        assert_eq!(retry_config.backoff(1_000), Duration::MAX);
+    }
+
+    #[test]
+    fn period_end_offset_lands_on_the_last_offset_of_the_period() {
+        let period = 3;
+        let end = period_end_offset(period);
+        assert_eq!((end + 1) % SHARD_OFFSET_MODULO, 0);
+        assert_eq!((end + 1) / SHARD_OFFSET_MODULO, period + 1);
+    }
+
+    #[test]
+    fn period_before_offset_is_the_prior_period() {
+        let period = 7;
+        let offset_in_period = period_end_offset(period) - 1;
+        assert_eq!(period_before_offset(offset_in_period), period - 1);
+    }
+
+    #[test]
+    fn skip_to_offset_advances_past_a_mid_period_failure() {
+        // A dead-lettered read issued mid-period: `skipped_period` is the same period
+        // `last_offset` is already in, and `period_end_offset` must still land past it.
+        let skipped_period = 5;
+        let last_offset = period_end_offset(skipped_period) - 10;
+        let skip_to_offset = period_end_offset(skipped_period);
+        assert!(skip_to_offset > last_offset);
+        assert_eq!((skip_to_offset + 1) % SHARD_OFFSET_MODULO, 0);
+    }
+
+    #[test]
+    fn skip_to_offset_advances_past_a_period_boundary_failure() {
+        // Regression test for the chunk0-2 boundary bug: a dead-lettered read that was the
+        // very first of `skipped_period`, issued from `Empty(period_end_offset(skipped_period - 1))`.
+        // The old fix derived the next period from `last_offset` alone, which only stepped one
+        // period past the *previous* period and landed back on the same `Empty` offset forever.
+        let skipped_period = 5;
+        let last_offset = period_end_offset(skipped_period - 1);
+        let skip_to_offset = period_end_offset(skipped_period);
+        assert!(skip_to_offset > last_offset);
+        assert_eq!((skip_to_offset + 1) % SHARD_OFFSET_MODULO, 0);
+    }
+
+    #[test]
+    fn prefetch_gate_keys_off_the_buffered_back_offset_not_last_offset() {
+        // Regression test for the chunk0-4 bug: gating on `last_offset` (already consumed)
+        // instead of the buffered back offset could start a prefetch that straddles a period
+        // boundary it shouldn't.
+        let period = 2;
+        let last_offset = period_end_offset(period) - 5; // not at a boundary
+        let last_buffered_offset = period_end_offset(period); // back row IS the period end
+        assert_ne!((last_offset + 1) % SHARD_OFFSET_MODULO, 0);
+        assert_eq!((last_buffered_offset + 1) % SHARD_OFFSET_MODULO, 0);
+    }
+}
+
@@ -0,0 +1,35 @@
+use crate::scylladb::types::{BlockchainEventType, ProducerId, ShardId, ShardOffset, ShardPeriod};
+
+/// A structured record describing a micro-batch or period-commit read that could not be
+/// recovered after exhausting its retry budget.
+#[derive(Debug, Clone)]
+pub(crate) struct DlqRecord {
+    pub producer_id: ProducerId,
+    pub shard_id: ShardId,
+    pub offset: ShardOffset,
+    pub period: ShardPeriod,
+    pub event_type: BlockchainEventType,
+    pub error: String,
+    pub attempts: usize,
+}
+
+/// Sink for [`DlqRecord`]s emitted when a `ShardIterator` gives up retrying a read.
+///
+/// `send` must not block the calling task for long; a sink backed by a slow downstream
+/// (Kafka, a file, ...) should buffer/batch internally rather than stall the iterator.
+pub(crate) trait DlqSink: Send + Sync {
+    fn send(&self, record: DlqRecord);
+}
+
+/// Default sink used when no DLQ backend is configured: logs and drops the record.
+#[derive(Default)]
+pub(crate) struct NoopDlqSink;
+
+impl DlqSink for NoopDlqSink {
+    fn send(&self, record: DlqRecord) {
+        tracing::warn!(
+            "dropping dead-lettered record, no DLQ sink configured: {:?}",
+            record
+        );
+    }
+}
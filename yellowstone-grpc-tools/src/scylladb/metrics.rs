@@ -0,0 +1,232 @@
+use std::{
+    collections::HashMap,
+    net::UdpSocket,
+    sync::Mutex,
+    time::Duration,
+};
+
+use scylla::routing::Shard;
+use tracing::warn;
+
+use crate::scylladb::types::{ProducerId, ShardId, ShardOffset};
+
+/// Fixed bucket boundaries (in milliseconds) for the fetch-latency histogram.
+const LATENCY_BUCKET_BOUNDS_MS: &[u64] = &[1, 5, 10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000];
+
+/// Pluggable destination for rendered metric lines. StatsD/UDP is the first implementation;
+/// anything else (Prometheus push gateway, a log sink, ...) just needs to implement this.
+pub(crate) trait MetricsBackend: Send + Sync {
+    fn emit(&self, lines: &[String]);
+}
+
+/// StatsD-over-UDP backend. Lines are newline-joined so a flush is a single syscall.
+pub(crate) struct StatsdBackend {
+    socket: UdpSocket,
+}
+
+impl StatsdBackend {
+    pub(crate) fn new(server_addr: &str) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(server_addr)?;
+        Ok(StatsdBackend { socket })
+    }
+}
+
+impl MetricsBackend for StatsdBackend {
+    fn emit(&self, lines: &[String]) {
+        if lines.is_empty() {
+            return;
+        }
+        let payload = lines.join("\n");
+        if let Err(e) = self.socket.send(payload.as_bytes()) {
+            warn!("failed to emit statsd metrics: {:?}", e);
+        }
+    }
+}
+
+struct Histogram {
+    bucket_counts: Vec<u64>,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            bucket_counts: vec![0; LATENCY_BUCKET_BOUNDS_MS.len() + 1],
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value_ms: u64) {
+        let idx = LATENCY_BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| value_ms <= *bound)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_MS.len());
+        self.bucket_counts[idx] += 1;
+        self.count += 1;
+    }
+
+    /// Upper bound of the bucket holding the `p`-th percentile (e.g. `p = 0.99` for p99).
+    fn percentile(&self, p: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let target = ((self.count as f64) * p).ceil() as u64;
+        let mut cumulative = 0u64;
+        for (idx, bucket_count) in self.bucket_counts.iter().enumerate() {
+            cumulative += bucket_count;
+            if cumulative >= target {
+                return *LATENCY_BUCKET_BOUNDS_MS
+                    .get(idx)
+                    .unwrap_or_else(|| LATENCY_BUCKET_BOUNDS_MS.last().unwrap());
+            }
+        }
+        *LATENCY_BUCKET_BOUNDS_MS.last().unwrap()
+    }
+}
+
+type ShardKey = (ProducerId, ShardId);
+
+#[derive(Default)]
+struct Inner {
+    fetch_latency: Histogram,
+    rows_emitted: HashMap<ShardKey, u64>,
+    state_transitions: HashMap<&'static str, u64>,
+    lag: HashMap<ShardKey, ShardOffset>,
+    routing_shard: HashMap<ShardKey, Shard>,
+}
+
+/// Metrics subsystem for [`crate::scylladb::consumer::shard_iterator::ShardIterator`].
+///
+/// Counters/timers are accumulated in memory and rendered to the configured
+/// [`MetricsBackend`] by a periodic flush task, so a hot shard never pays one syscall per event.
+pub(crate) struct ShardMetrics {
+    backend: Box<dyn MetricsBackend>,
+    inner: Mutex<Inner>,
+}
+
+impl ShardMetrics {
+    pub(crate) fn new(backend: Box<dyn MetricsBackend>) -> Self {
+        ShardMetrics {
+            backend,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    pub(crate) fn record_fetch_latency(&self, latency: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.fetch_latency.observe(latency.as_millis() as u64);
+    }
+
+    pub(crate) fn record_rows_emitted(&self, producer_id: ProducerId, shard_id: ShardId, rows: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.rows_emitted.entry((producer_id, shard_id)).or_default() += rows;
+    }
+
+    pub(crate) fn record_state_transition(&self, state: &'static str) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner.state_transitions.entry(state).or_default() += 1;
+    }
+
+    /// `current_offset - last_committed_period_offset`, the number of shard offsets the
+    /// consumer's current offset lies past the last offset of the last fully-committed period.
+    pub(crate) fn set_lag(&self, producer_id: ProducerId, shard_id: ShardId, lag: ShardOffset) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.lag.insert((producer_id, shard_id), lag);
+    }
+
+    /// Records the ScyllaDB shard the most recent read for `(producer_id, shard_id)` landed on.
+    /// Purely observational — the driver picks the connection itself; this just lets the
+    /// flushed metrics show whether reads are spread across shards as expected.
+    pub(crate) fn record_routing_shard(&self, producer_id: ProducerId, shard_id: ShardId, shard: Shard) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.routing_shard.insert((producer_id, shard_id), shard);
+    }
+
+    /// Renders accumulated counters/timers to statsd-style lines and resets them.
+    fn flush(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut lines = Vec::new();
+
+        lines.push(format!("shard_iterator.fetch_latency_ms.p50:{}|g", inner.fetch_latency.percentile(0.50)));
+        lines.push(format!("shard_iterator.fetch_latency_ms.p90:{}|g", inner.fetch_latency.percentile(0.90)));
+        lines.push(format!("shard_iterator.fetch_latency_ms.p99:{}|g", inner.fetch_latency.percentile(0.99)));
+
+        for ((producer_id, shard_id), rows) in inner.rows_emitted.iter() {
+            lines.push(format!("shard_iterator.rows_emitted:{}|c|#producer_id:{},shard_id:{}", rows, producer_id, shard_id));
+        }
+        for (state, count) in inner.state_transitions.iter() {
+            lines.push(format!("shard_iterator.state_transition:{}|c|#state:{}", count, state));
+        }
+        for ((producer_id, shard_id), lag) in inner.lag.iter() {
+            lines.push(format!("shard_iterator.lag:{}|g|#producer_id:{},shard_id:{}", lag, producer_id, shard_id));
+        }
+        for ((producer_id, shard_id), shard) in inner.routing_shard.iter() {
+            lines.push(format!("shard_iterator.routing_shard:{:?}|g|#producer_id:{},shard_id:{}", shard, producer_id, shard_id));
+        }
+
+        self.backend.emit(&lines);
+
+        inner.fetch_latency = Histogram::new();
+        inner.rows_emitted.clear();
+        inner.state_transitions.clear();
+        // lag is a gauge of current state, not an increment - keep the last known value around.
+    }
+
+    /// Spawns a task that periodically flushes accumulated metrics to the backend.
+    pub(crate) fn spawn_flush_task(self: std::sync::Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                self.flush();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_histogram_does_not_panic_on_first_observe() {
+        // Regression test for the chunk0-3 bug: `Inner::default()` used to build `fetch_latency`
+        // via a derived `Histogram::default()` that left `bucket_counts` empty, so the very first
+        // `observe()` indexed out of bounds.
+        let mut histogram = Histogram::default();
+        histogram.observe(1);
+        assert_eq!(histogram.percentile(1.0), 1);
+    }
+
+    #[test]
+    fn percentile_picks_the_bucket_upper_bound() {
+        let mut histogram = Histogram::new();
+        for _ in 0..100 {
+            histogram.observe(1);
+        }
+        histogram.observe(5_000);
+        assert_eq!(histogram.percentile(0.50), 1);
+        assert_eq!(histogram.percentile(0.99), 1);
+        assert_eq!(histogram.percentile(1.0), 5_000);
+    }
+
+    #[test]
+    fn percentile_of_empty_histogram_is_zero() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.percentile(0.99), 0);
+    }
+
+    #[test]
+    fn observe_above_highest_bound_falls_into_the_overflow_bucket() {
+        let mut histogram = Histogram::new();
+        histogram.observe(u64::MAX);
+        assert_eq!(histogram.percentile(1.0), *LATENCY_BUCKET_BOUNDS_MS.last().unwrap());
+    }
+}